@@ -1,8 +1,14 @@
 use clap::Parser;
 use env_logger;
-use log::info;
+use log::{info, warn};
 use std::io::Write;
-use sup_rs::{config::config::Config, controller::server::Server};
+use sup_rs::{
+    config::config::Config,
+    controller::{
+        command::{TcpSocketTp, UnixSocketTp, VsockTp},
+        server::{self, Server},
+    },
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -34,6 +40,45 @@ async fn main() {
         Ok(c) => c,
         Err(e) => panic!("create config failed: {}", e.to_string()),
     };
+    let listen = cfg.sup.listen.clone();
+
     info!("server start");
-    Server::new(cfg.sup.socket).await.unwrap().run().await;
+    let mut sup = Server::new(cfg).unwrap();
+    sup.start().unwrap();
+    sup.install();
+
+    // `Transport<T>` is generic per `T`, so the three schemes can't share
+    // one variable; each branch picks its concrete transport and hands it
+    // to the same dispatch loop.
+    match listen.split_once(':') {
+        Some(("unix", path)) => server::serve_forever(UnixSocketTp::new(path.to_string())),
+        Some(("tcp", addr)) => {
+            warn!(
+                "listening on tcp:{} — this carries no authentication, encryption, or peer ACL; \
+                 anyone who can reach it can Start/Stop/Kill the supervised program or Spawn an \
+                 arbitrary one, only bind it on a trusted network",
+                addr
+            );
+            server::serve_forever(TcpSocketTp::new(addr.to_string()))
+        }
+        Some(("vsock", rest)) => {
+            let (cid, port) = rest
+                .split_once(':')
+                .unwrap_or_else(|| panic!("vsock listen address must be cid:port, got {}", rest));
+            warn!(
+                "listening on vsock:{}:{} — this carries no authentication, encryption, or peer \
+                 ACL; anyone who can reach it can Start/Stop/Kill the supervised program or Spawn \
+                 an arbitrary one, only bind it on a trusted CID",
+                cid, port
+            );
+            server::serve_forever(VsockTp::new(
+                cid.parse().expect("vsock cid must be a u32"),
+                port.parse().expect("vsock port must be a u32"),
+            ))
+        }
+        _ => panic!(
+            "unsupported listen scheme {:?}, expected unix:/tcp:/vsock:",
+            listen
+        ),
+    }
 }
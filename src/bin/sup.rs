@@ -0,0 +1,244 @@
+use clap::{Parser, Subcommand};
+use sup_rs::{
+    config::config::Config,
+    controller::{
+        command::{
+            Command, Request, Response, ResponseFormat, TcpSocketTp, Transport, UnixSocketTp,
+            VsockTp,
+        },
+        error::ProcessErr,
+        frame::{Frame, FrameKind},
+        spawn::SpawnArgs,
+        wire,
+    },
+};
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    // path to toml format config file
+    #[arg(short, long)]
+    config_path: String,
+
+    /// print the response as JSON instead of the default human-readable
+    /// text, so e.g. `sup status --json` can be consumed by other programs
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    cmd: Cli,
+}
+
+/// Client-side subcommands. A superset of [`Command`]'s bare opcode: it
+/// carries the arguments (a spawned-process handle, or a full
+/// `program`/`args`/`env`/`cwd` for `Spawn`) that `Command` itself has no
+/// room for, since `Command` is shared with the wire opcode byte and stays
+/// a plain tag there.
+#[derive(Debug, Subcommand)]
+enum Cli {
+    #[command(about = "start program asynchronously")]
+    Start,
+    #[command(about = "stop program asynchronously")]
+    Stop {
+        /// handle of a process launched via `spawn`; omit to target the
+        /// single preconfigured program
+        handle: Option<String>,
+    },
+    #[command(about = "restart program asynchronously")]
+    Restart,
+    #[command(about = "kill program (only its own pid; processes it started are not signalled)")]
+    Kill {
+        /// handle of a process launched via `spawn`; omit to target the
+        /// single preconfigured program
+        handle: Option<String>,
+    },
+    #[command(about = "reload program without dropping its listening sockets")]
+    Reload,
+    #[command(about = "print status of program")]
+    Status {
+        /// handle of a process launched via `spawn`; omit to target the
+        /// single preconfigured program
+        handle: Option<String>,
+    },
+    #[command(about = "exit the sup daemon and the process asynchronously")]
+    Exit,
+    #[command(about = "attach to the program's stdout/stderr and tail it live")]
+    Attach,
+    #[command(about = "replay the program's captured stdout/stderr then exit")]
+    Logs,
+    #[command(about = "spawn an arbitrary one-off process under supervision")]
+    Spawn {
+        /// program to execute
+        program: String,
+        /// arguments passed to `program`
+        #[arg(trailing_var_arg = true)]
+        args: Vec<String>,
+        /// environment variable to set, as KEY=VALUE; may be repeated
+        #[arg(long = "env", value_parser = parse_env_kv)]
+        env: Vec<(String, String)>,
+        /// working directory to launch `program` in
+        #[arg(long)]
+        cwd: Option<String>,
+    },
+}
+
+fn parse_env_kv(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .ok_or_else(|| format!("invalid --env entry {:?}, expected KEY=VALUE", s))
+}
+
+/// Splits a `Cli` invocation into the wire-level `Command` tag plus its
+/// opaque `args` payload (see [`Request::args`]).
+fn into_request(cli: Cli, format: ResponseFormat) -> Request {
+    let handle_args = |handle: Option<String>| handle.unwrap_or_default().into_bytes();
+    match cli {
+        Cli::Start => Request::new(Command::Start, format, Vec::new()),
+        Cli::Stop { handle } => Request::new(Command::Stop, format, handle_args(handle)),
+        Cli::Restart => Request::new(Command::Restart, format, Vec::new()),
+        Cli::Kill { handle } => Request::new(Command::Kill, format, handle_args(handle)),
+        Cli::Reload => Request::new(Command::Reload, format, Vec::new()),
+        Cli::Status { handle } => Request::new(Command::Status, format, handle_args(handle)),
+        Cli::Exit => Request::new(Command::Exit, format, Vec::new()),
+        Cli::Attach => Request::new(Command::Attach, format, Vec::new()),
+        Cli::Logs => Request::new(Command::Logs, format, Vec::new()),
+        Cli::Spawn {
+            program,
+            args,
+            env,
+            cwd,
+        } => {
+            let spawn_args = SpawnArgs {
+                program,
+                args,
+                env,
+                cwd,
+            };
+            let encoded = spawn_args
+                .encode()
+                .unwrap_or_else(|e| panic!("encode spawn args failed: {}", e));
+            Request::new(Command::Spawn, format, encoded)
+        }
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+    let cfg = match Config::new(&args.config_path) {
+        Ok(c) => c,
+        Err(e) => panic!("create config failed: {}", e.to_string()),
+    };
+    let listen = cfg.sup.listen.clone();
+
+    let format = if args.json {
+        ResponseFormat::Json
+    } else {
+        ResponseFormat::Binary
+    };
+    let req = into_request(args.cmd, format);
+
+    // `Transport<T>` is generic per `T`, so the three schemes can't share
+    // one variable; each branch picks its concrete transport and hands it
+    // to the same send-and-print flow.
+    match listen.split_once(':') {
+        Some(("unix", path)) => send(UnixSocketTp::new(path.to_string()), req),
+        Some(("tcp", addr)) => send(TcpSocketTp::new(addr.to_string()), req),
+        Some(("vsock", rest)) => {
+            let (cid, port) = rest
+                .split_once(':')
+                .unwrap_or_else(|| panic!("vsock listen address must be cid:port, got {}", rest));
+            send(
+                VsockTp::new(
+                    cid.parse().expect("vsock cid must be a u32"),
+                    port.parse().expect("vsock port must be a u32"),
+                ),
+                req,
+            )
+        }
+        _ => panic!(
+            "unsupported listen scheme {:?}, expected unix:/tcp:/vsock:",
+            listen
+        ),
+    }
+}
+
+/// Connects, runs the version handshake, sends one `Request` and prints
+/// whatever comes back, generic over which concrete `Transport` the
+/// daemon is configured to listen on (mirrors `server::serve_forever`'s
+/// dispatch over the accept side). `Attach`/`Logs` reply with a stream of
+/// `Frame`s instead of one `Response`; everything else gets exactly one.
+fn send<Tp, T>(mut transport: Tp, req: Request)
+where
+    Tp: Transport<T>,
+    T: std::io::Read + std::io::Write,
+{
+    let is_stream = matches!(&req.cmd, Command::Attach | Command::Logs);
+    let is_attach = matches!(&req.cmd, Command::Attach);
+
+    transport.connect();
+    let stream = transport.stream().expect("connect must run before send");
+
+    if let Err(e) = wire::negotiate_client(stream) {
+        panic!("version handshake failed: {}", e);
+    }
+
+    let payload: Vec<u8> = req.into();
+    if let Err(e) =
+        wire::write_message(stream, wire::PROTOCOL_VERSION, wire::MsgType::Request, &payload)
+    {
+        panic!("send request failed: {}", e);
+    }
+
+    if is_stream {
+        print_frames(stream, is_attach);
+        return;
+    }
+
+    let (_, msg_type, payload) = match wire::read_message(stream) {
+        Ok(m) => m,
+        Err(e) => panic!("read response failed: {}", e),
+    };
+
+    match msg_type {
+        wire::MsgType::ResponseJson => println!("{}", String::from_utf8_lossy(&payload)),
+        wire::MsgType::Response => match Response::try_from(payload) {
+            Ok(resp) => println!("{}", resp),
+            Err(e) => panic!("malformed response: {}", e),
+        },
+        other => panic!("unexpected message type {:?}", other),
+    }
+}
+
+/// Prints every `Frame` the daemon streams back for `Attach`/`Logs`:
+/// stdout/stderr frames go to the matching stream, an exit frame ends an
+/// `Attach` tail (`stop_at_exit`), and the daemon closing the connection
+/// ends it just as cleanly. `Logs` replays a fixed backlog that can hold an
+/// earlier run's exit frame followed by later output, so it skips exit
+/// frames and ends only when the daemon closes the connection.
+fn print_frames(stream: &mut impl std::io::Read, stop_at_exit: bool) {
+    use std::io::Write;
+    loop {
+        let (_, msg_type, payload) = match wire::read_message(stream) {
+            Ok(m) => m,
+            Err(ProcessErr::StreamClosed) => return,
+            Err(e) => panic!("read frame failed: {}", e),
+        };
+        if msg_type != wire::MsgType::Frame {
+            panic!("unexpected message type {:?}, expected a Frame", msg_type);
+        }
+        let frame = match Frame::decode(payload) {
+            Ok(f) => f,
+            Err(e) => panic!("malformed frame: {}", e),
+        };
+        match frame.kind {
+            FrameKind::Stdout => {
+                let _ = std::io::stdout().write_all(&frame.payload);
+            }
+            FrameKind::Stderr => {
+                let _ = std::io::stderr().write_all(&frame.payload);
+            }
+            FrameKind::Exit if stop_at_exit => return,
+            FrameKind::Exit => {}
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::fmt::{self, Display};
+use std::fs;
+
+use serde::Deserialize;
+
+#[derive(Debug)]
+pub enum ConfigErr {
+    ReadFileFailed(String),
+    ParseFailed(String),
+}
+
+impl Display for ConfigErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigErr::ReadFileFailed(e) => write!(f, "read config file failed: {}", e),
+            ConfigErr::ParseFailed(e) => write!(f, "parse config file failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigErr {}
+
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    pub sup: SupConfig,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SupConfig {
+    /// Where the daemon listens for client commands, as a scheme-prefixed
+    /// URI: `unix:/path/to.sock`, `tcp:0.0.0.0:7000`, or `vsock:3:7000`
+    /// (a VM's context id and port) for supervising a process remotely.
+    ///
+    /// `tcp:`/`vsock:` carry no authentication, encryption, or peer ACL of
+    /// any kind: anyone who can reach the address can `Start`/`Stop`/`Kill`
+    /// the supervised program or `Spawn` an arbitrary one of their own —
+    /// i.e. unauthenticated remote code execution. Only use a network
+    /// scheme on an address reachable solely from a trusted network (a
+    /// host-only vsock CID, a firewalled management VLAN); prefer `unix:`
+    /// with filesystem permissions wherever the daemon and its clients
+    /// share a host.
+    pub listen: String,
+    /// Program the daemon supervises, plus the arguments it is launched
+    /// with.
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// TCP listeners that should survive a `Reload`: each entry is a
+    /// `host:port` address, bound by the supervisor itself (not the
+    /// program) so the fd exists before the child is even spawned, then
+    /// handed to every child (first start and every reload alike) over
+    /// the control socket. There is currently no way to preserve a Unix
+    /// socket this way. The child must pick the fds up itself — dup2 them
+    /// onto `LISTEN_FDS_START..` per the `LISTEN_FDS`/`LISTEN_PID`
+    /// contract (see [`crate::controller::fdpass`]) — this crate has no
+    /// child-side helper or example program that does so yet.
+    #[serde(default)]
+    pub preserve_sockets: Vec<String>,
+}
+
+impl Config {
+    pub fn new(path: &str) -> Result<Self, ConfigErr> {
+        let content =
+            fs::read_to_string(path).map_err(|e| ConfigErr::ReadFileFailed(e.to_string()))?;
+        toml::from_str(&content).map_err(|e| ConfigErr::ParseFailed(e.to_string()))
+    }
+}
@@ -2,6 +2,7 @@ use std::{
     fmt::Display,
     fs,
     io::{Read, Write},
+    net::{TcpListener, TcpStream},
     ops::Index,
     os::unix::net::{UnixListener, UnixStream},
     path::Path,
@@ -9,6 +10,8 @@ use std::{
 
 use clap::{error::ErrorKind, Error, FromArgMatches, Subcommand};
 use log::error;
+use serde::Serialize;
+use vsock::{VsockAddr, VsockListener, VsockStream};
 
 const BYTES_PER_PID: usize = 4;
 
@@ -18,9 +21,18 @@ use crossbeam::{
 };
 
 use super::error::ProcessErr;
+use super::frame::Frame;
 
 pub trait CommandHandler {
     fn handle_command(r: Request) -> Response;
+
+    /// Streaming variant for commands that tail a child's output
+    /// (`Attach`, `Logs`) instead of replying with a single `Response`:
+    /// for `Attach` the returned receiver yields live [`Frame`]s (a
+    /// `FrameKind::Exit` frame when the program exits) for as long as it
+    /// is held; for `Logs` it yields the captured backlog, then
+    /// disconnects.
+    fn handle_stream(r: Request) -> Receiver<Frame>;
 }
 
 pub trait Transport<T>
@@ -30,57 +42,48 @@ where
     fn connect(&mut self);
     fn serve(&self);
     fn read(&self) -> Result<T, ProcessErr>;
-    fn write(self, v: Vec<u8>) -> Result<T, ProcessErr>;
+
+    /// The connected stream, for a client that needs to drive
+    /// [`super::wire`] directly (a handshake followed by a request, read
+    /// on the same connection) instead of the one-shot `read` above.
+    /// `None` until `connect` has been called.
+    fn stream(&mut self) -> Option<&mut T>;
 }
 
-pub struct UnixSocketTp {
-    socket_path: String,
-    stream: Option<UnixStream>,
-    listen_recv: Option<Receiver<UnixStream>>,
-    listen_send: Option<Sender<UnixStream>>,
+/// Accept-loop/channel-handoff plumbing shared by every `Transport` impl
+/// below: `serve`/`read` were byte-for-byte copies of each other
+/// differing only in the stream type `T`, since accepting a connection
+/// and handing it to whichever thread calls `read()` doesn't care whether
+/// `T` is a `UnixStream`, `TcpStream`, or `VsockStream`. Only binding and
+/// connecting are genuinely transport-specific, so those stay on
+/// `UnixSocketTp`/`TcpSocketTp`/`VsockTp` themselves.
+struct ChannelTp<T> {
+    stream: Option<T>,
+    listen_recv: Option<Receiver<T>>,
+    listen_send: Option<Sender<T>>,
 }
 
-impl UnixSocketTp {
-    pub fn new(socket_path: String) -> Self {
+impl<T> ChannelTp<T> {
+    fn new() -> Self {
         let (s, r) = unbounded();
         Self {
-            socket_path,
             stream: None,
             listen_recv: Some(r),
             listen_send: Some(s),
         }
     }
-}
-
-impl Transport<UnixStream> for UnixSocketTp {
-    fn connect(&mut self) {
-        let stream = match UnixStream::connect(self.socket_path.as_str()) {
-            Err(e) => panic!("connect to socket {} failed: {}", self.socket_path, e),
-            Ok(stream) => stream,
-        };
-        self.stream = Some(stream);
-    }
-
-    fn serve(&self) {
-        if Path::new(self.socket_path.as_str()).exists() {
-            fs::remove_file(self.socket_path.as_str()).unwrap();
-        }
-
-        let listener = match UnixListener::bind(self.socket_path.as_str()) {
-            Err(e) => panic!("bind socket {} failed: {}", self.socket_path, e),
-            Ok(l) => l,
-        };
 
+    fn accept_loop(&self, accept: impl Fn() -> std::io::Result<T>) {
         loop {
-            let (unix_stream, _) = match listener.accept() {
-                Ok((s, a)) => (s, a),
+            let stream = match accept() {
+                Ok(s) => s,
                 Err(e) => {
                     error!("accept stream failed: {}", e);
                     continue;
                 }
             };
             match &self.listen_send {
-                Some(s) => match s.send(unix_stream) {
+                Some(s) => match s.send(stream) {
                     Ok(_) => {}
                     Err(e) => error!("send to channel failed: {}", e),
                 },
@@ -89,7 +92,7 @@ impl Transport<UnixStream> for UnixSocketTp {
         }
     }
 
-    fn read(&self) -> Result<UnixStream, ProcessErr> {
+    fn read(&self) -> Result<T, ProcessErr> {
         match &self.listen_recv {
             Some(rcv) => {
                 select! {
@@ -109,27 +112,155 @@ impl Transport<UnixStream> for UnixSocketTp {
         }
     }
 
-    // TODO: convert self to &mut self?
-    fn write(self, v: Vec<u8>) -> Result<UnixStream, ProcessErr> {
-        match self.stream {
-            Some(mut s) => match s.write(v.index(..)) {
-                Ok(_) => {
-                    if let Err(e) = s.shutdown(std::net::Shutdown::Write) {
-                        return Err(ProcessErr::ShutdownStreamFailed(
-                            "write".to_string(),
-                            e.to_string(),
-                        ));
-                    }
-                    Ok(s)
-                }
-                Err(e) => Err(ProcessErr::WriteToStreamFailed(e.to_string())),
-            },
-            None => Err(ProcessErr::StreamUsedBeforeInited("write".to_string())),
+    fn stream_mut(&mut self) -> Option<&mut T> {
+        self.stream.as_mut()
+    }
+
+    fn set_stream(&mut self, stream: T) {
+        self.stream = Some(stream);
+    }
+}
+
+pub struct UnixSocketTp {
+    socket_path: String,
+    inner: ChannelTp<UnixStream>,
+}
+
+impl UnixSocketTp {
+    pub fn new(socket_path: String) -> Self {
+        Self {
+            socket_path,
+            inner: ChannelTp::new(),
+        }
+    }
+}
+
+impl Transport<UnixStream> for UnixSocketTp {
+    fn connect(&mut self) {
+        let stream = match UnixStream::connect(self.socket_path.as_str()) {
+            Err(e) => panic!("connect to socket {} failed: {}", self.socket_path, e),
+            Ok(stream) => stream,
+        };
+        self.inner.set_stream(stream);
+    }
+
+    fn serve(&self) {
+        if Path::new(self.socket_path.as_str()).exists() {
+            fs::remove_file(self.socket_path.as_str()).unwrap();
+        }
+
+        let listener = match UnixListener::bind(self.socket_path.as_str()) {
+            Err(e) => panic!("bind socket {} failed: {}", self.socket_path, e),
+            Ok(l) => l,
+        };
+
+        self.inner.accept_loop(|| listener.accept().map(|(s, _)| s));
+    }
+
+    fn read(&self) -> Result<UnixStream, ProcessErr> {
+        self.inner.read()
+    }
+
+    fn stream(&mut self) -> Option<&mut UnixStream> {
+        self.inner.stream_mut()
+    }
+}
+
+pub struct TcpSocketTp {
+    addr: String,
+    inner: ChannelTp<TcpStream>,
+}
+
+impl TcpSocketTp {
+    pub fn new(addr: String) -> Self {
+        Self {
+            addr,
+            inner: ChannelTp::new(),
+        }
+    }
+}
+
+impl Transport<TcpStream> for TcpSocketTp {
+    fn connect(&mut self) {
+        let stream = match TcpStream::connect(self.addr.as_str()) {
+            Err(e) => panic!("connect to {} failed: {}", self.addr, e),
+            Ok(stream) => stream,
+        };
+        self.inner.set_stream(stream);
+    }
+
+    fn serve(&self) {
+        let listener = match TcpListener::bind(self.addr.as_str()) {
+            Err(e) => panic!("bind {} failed: {}", self.addr, e),
+            Ok(l) => l,
+        };
+
+        self.inner.accept_loop(|| listener.accept().map(|(s, _)| s));
+    }
+
+    fn read(&self) -> Result<TcpStream, ProcessErr> {
+        self.inner.read()
+    }
+
+    fn stream(&mut self) -> Option<&mut TcpStream> {
+        self.inner.stream_mut()
+    }
+}
+
+/// Supervises a process over AF_VSOCK instead of a filesystem/network
+/// socket, so a `sup` daemon on the host can drive a process inside a VM
+/// or lightweight container by its context id (CID) and port, the way
+/// p9cpu-style VM supervisors do.
+pub struct VsockTp {
+    cid: u32,
+    port: u32,
+    inner: ChannelTp<VsockStream>,
+}
+
+impl VsockTp {
+    pub fn new(cid: u32, port: u32) -> Self {
+        Self {
+            cid,
+            port,
+            inner: ChannelTp::new(),
         }
     }
 }
 
-#[derive(Debug, Subcommand)]
+impl Transport<VsockStream> for VsockTp {
+    fn connect(&mut self) {
+        let stream = match VsockStream::connect(&VsockAddr::new(self.cid, self.port)) {
+            Err(e) => panic!(
+                "connect to vsock cid {} port {} failed: {}",
+                self.cid, self.port, e
+            ),
+            Ok(stream) => stream,
+        };
+        self.inner.set_stream(stream);
+    }
+
+    fn serve(&self) {
+        let listener = match VsockListener::bind(&VsockAddr::new(self.cid, self.port)) {
+            Err(e) => panic!(
+                "bind vsock cid {} port {} failed: {}",
+                self.cid, self.port, e
+            ),
+            Ok(l) => l,
+        };
+
+        self.inner.accept_loop(|| listener.accept().map(|(s, _)| s));
+    }
+
+    fn read(&self) -> Result<VsockStream, ProcessErr> {
+        self.inner.read()
+    }
+
+    fn stream(&mut self) -> Option<&mut VsockStream> {
+        self.inner.stream_mut()
+    }
+}
+
+#[derive(Debug, Clone, Subcommand)]
 pub enum Command {
     #[command(about = "start program asynchronously")]
     Start,
@@ -139,12 +270,18 @@ pub enum Command {
     Restart,
     #[command(about = "kill program and all child processes")]
     Kill,
-    #[command(about = "reload program")]
+    #[command(about = "reload program without dropping its listening sockets")]
     Reload,
     #[command(about = "print status of program")]
     Status,
     #[command(about = "exit the sup daemon and the process asynchronously")]
     Exit,
+    #[command(about = "attach to the program's stdout/stderr and tail it live")]
+    Attach,
+    #[command(about = "replay the program's captured stdout/stderr then exit")]
+    Logs,
+    #[command(about = "spawn an arbitrary one-off process under supervision")]
+    Spawn,
 
     #[command(skip)]
     Unknown,
@@ -201,20 +338,81 @@ impl From<&str> for Command {
             "reload" => Command::Reload,
             "status" => Command::Status,
             "exit" => Command::Exit,
+            "attach" => Command::Attach,
+            "logs" => Command::Logs,
+            "spawn" => Command::Spawn,
             _ => Command::Unknown,
         }
     }
 }
 
+/// How a client wants its `Response` back: the default compact binary
+/// marshal, or JSON for scripting (`sup status --json`). Carried as part
+/// of `Request` rather than negotiated once per connection, since a
+/// connection only ever carries one request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseFormat {
+    Binary,
+    Json,
+}
+
+impl Default for ResponseFormat {
+    fn default() -> Self {
+        ResponseFormat::Binary
+    }
+}
+
+impl ResponseFormat {
+    fn to_byte(self) -> u8 {
+        match self {
+            ResponseFormat::Binary => 0,
+            ResponseFormat::Json => 1,
+        }
+    }
+
+    fn from_byte(b: u8) -> Self {
+        match b {
+            1 => ResponseFormat::Json,
+            _ => ResponseFormat::Binary,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Request {
     pub cmd: Command,
+    /// Which shape the caller wants `Response` marshaled back in.
+    pub format: ResponseFormat,
+    /// Opaque body following the opcode: a [`super::spawn::SpawnArgs`] for
+    /// `Spawn`, a handle string for `Status`/`Stop`/`Kill` targeting a
+    /// spawned process, or empty when the request targets the single
+    /// preconfigured program.
+    pub args: Vec<u8>,
 }
 
-#[derive(Debug)]
+impl Request {
+    pub fn new(cmd: Command, format: ResponseFormat, args: Vec<u8>) -> Self {
+        Self { cmd, format, args }
+    }
+}
+
+/// A spawned process's handle and pid, as reported by `Status`'s JSON
+/// response. Not carried by the compact binary marshal (see
+/// `From<Response> for Vec<u8>`), which has no room for a variable-length
+/// table — `sup status` without `--json` only ever reports the single
+/// preconfigured program.
+#[derive(Debug, Serialize)]
+pub struct SpawnedInfo {
+    pub handle: String,
+    pub pid: u32,
+}
+
+#[derive(Debug, Serialize)]
 pub struct Response {
     message: String,
     sup_pid: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    spawned: Vec<SpawnedInfo>,
 }
 
 impl Display for Response {
@@ -228,7 +426,29 @@ impl Display for Response {
 
 impl Response {
     pub fn new(message: String, sup_pid: Option<u32>) -> Self {
-        Self { message, sup_pid }
+        Self {
+            message,
+            sup_pid,
+            spawned: Vec::new(),
+        }
+    }
+
+    /// Like `new`, but additionally reports every process launched via
+    /// `Command::Spawn` — only meaningful on the JSON path (`to_json`);
+    /// the binary marshal below drops `spawned` entirely.
+    pub fn new_with_spawned(message: String, sup_pid: Option<u32>, spawned: Vec<SpawnedInfo>) -> Self {
+        Self {
+            message,
+            sup_pid,
+            spawned,
+        }
+    }
+
+    /// Serializes this response to JSON instead of the compact binary
+    /// marshal below, for a client that asked for `ResponseFormat::Json`
+    /// (`sup status --json`, for scripting against).
+    pub fn to_json(&self) -> Result<Vec<u8>, ProcessErr> {
+        serde_json::to_vec(self).map_err(|e| ProcessErr::InvalidUtf8(e.to_string()))
     }
 }
 
@@ -245,6 +465,9 @@ impl From<Vec<u8>> for Command {
             4 => Self::Reload,
             5 => Self::Status,
             6 => Self::Exit,
+            7 => Self::Attach,
+            8 => Self::Logs,
+            9 => Self::Spawn,
             _ => Self::Unknown,
         }
     }
@@ -260,20 +483,39 @@ impl From<Command> for Vec<u8> {
             Command::Reload => vec![4],
             Command::Status => vec![5],
             Command::Exit => vec![6],
-            Command::Unknown => vec![7],
+            Command::Attach => vec![7],
+            Command::Logs => vec![8],
+            Command::Spawn => vec![9],
+            Command::Unknown => vec![10],
         }
     }
 }
 
 impl From<Vec<u8>> for Request {
     fn from(v: Vec<u8>) -> Self {
-        Self { cmd: v.into() }
+        if v.len() < 2 {
+            return Self {
+                cmd: Command::Unknown,
+                format: ResponseFormat::Binary,
+                args: Vec::new(),
+            };
+        }
+        let (opcode, rest) = v.split_at(1);
+        let (format, args) = rest.split_at(1);
+        Self {
+            cmd: opcode.to_vec().into(),
+            format: ResponseFormat::from_byte(format[0]),
+            args: args.to_vec(),
+        }
     }
 }
 
 impl From<Request> for Vec<u8> {
     fn from(r: Request) -> Self {
-        r.cmd.into()
+        let mut out: Vec<u8> = r.cmd.into();
+        out.push(r.format.to_byte());
+        out.extend(r.args);
+        out
     }
 }
 
@@ -286,15 +528,21 @@ impl From<Response> for Vec<u8> {
     }
 }
 
-impl From<Vec<u8>> for Response {
-    fn from(v: Vec<u8>) -> Self {
+impl TryFrom<Vec<u8>> for Response {
+    type Error = ProcessErr;
+
+    fn try_from(v: Vec<u8>) -> Result<Self, ProcessErr> {
+        if v.len() < BYTES_PER_PID {
+            return Err(ProcessErr::TruncatedMessage);
+        }
         let mut s = Self {
             message: String::new(),
             sup_pid: Some(0),
+            spawned: Vec::new(),
         };
         s.unmarshal_sup_pid(v.index(..BYTES_PER_PID).to_vec());
-        s.unmarshal_msg(v.index(BYTES_PER_PID..).to_vec());
-        s
+        s.unmarshal_msg(v.index(BYTES_PER_PID..).to_vec())?;
+        Ok(s)
     }
 }
 
@@ -318,18 +566,19 @@ impl Response {
         ]
     }
 
-    fn unmarshal_msg(&mut self, v: Vec<u8>) {
-        self.message = String::from_utf8(v).unwrap();
+    fn unmarshal_msg(&mut self, v: Vec<u8>) -> Result<(), ProcessErr> {
+        self.message = String::from_utf8(v).map_err(|e| ProcessErr::InvalidUtf8(e.to_string()))?;
+        Ok(())
     }
 
     fn unmarshal_sup_pid(&mut self, v: Vec<u8>) {
-        let mut pid = match self.sup_pid {
-            Some(pid) => pid,
-            None => return,
-        };
-        for (i, e) in v.into_iter().enumerate() {
-            pid += (e as u32) << (8 * i);
+        if self.sup_pid.is_none() {
+            return;
         }
+        // Caller always slices exactly BYTES_PER_PID bytes off the front
+        // of the response (see `TryFrom<Vec<u8>> for Response`).
+        let bytes: [u8; BYTES_PER_PID] = v.try_into().expect("pid field is BYTES_PER_PID bytes");
+        let pid = u32::from_be_bytes(bytes);
 
         if pid == Self::NONE_PID {
             self.sup_pid = None;
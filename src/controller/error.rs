@@ -0,0 +1,74 @@
+use std::fmt::{self, Display};
+
+#[derive(Debug)]
+pub enum ProcessErr {
+    ReadFromChannelFail(String),
+    ChannelUsedBeforeInited(String),
+    StreamUsedBeforeInited(String),
+    ShutdownStreamFailed(String, String),
+    WriteToStreamFailed(String),
+    SendFdFailed(String),
+    RecvFdFailed(String),
+    ReadinessTimeout,
+    ChildSpawnFailed(String),
+    UnknownFrameKind(u8),
+    StreamClosed,
+    BadMagic(u8),
+    UnknownMsgType(u8),
+    UnexpectedMsgType,
+    UnsupportedVersion(u8, u8),
+    InvalidUtf8(String),
+    TruncatedMessage,
+    UnknownHandle(String),
+    AlreadyRunning(u32),
+    PayloadTooLarge(u32),
+}
+
+impl Display for ProcessErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProcessErr::ReadFromChannelFail(e) => write!(f, "read from channel failed: {}", e),
+            ProcessErr::ChannelUsedBeforeInited(op) => {
+                write!(f, "channel used before inited: {}", op)
+            }
+            ProcessErr::StreamUsedBeforeInited(op) => {
+                write!(f, "stream used before inited: {}", op)
+            }
+            ProcessErr::ShutdownStreamFailed(op, e) => {
+                write!(f, "shutdown stream after {} failed: {}", op, e)
+            }
+            ProcessErr::WriteToStreamFailed(e) => write!(f, "write to stream failed: {}", e),
+            ProcessErr::SendFdFailed(e) => write!(f, "send fd over control socket failed: {}", e),
+            ProcessErr::RecvFdFailed(e) => {
+                write!(f, "recv fd over control socket failed: {}", e)
+            }
+            ProcessErr::ReadinessTimeout => {
+                write!(f, "reloaded child did not signal readiness in time")
+            }
+            ProcessErr::ChildSpawnFailed(e) => write!(f, "spawn child process failed: {}", e),
+            ProcessErr::UnknownFrameKind(b) => write!(f, "unknown frame kind byte: {}", b),
+            ProcessErr::StreamClosed => write!(f, "stream closed before a full message arrived"),
+            ProcessErr::BadMagic(b) => write!(f, "bad magic byte: {:#x}, not a sup wire message", b),
+            ProcessErr::UnknownMsgType(b) => write!(f, "unknown message type byte: {}", b),
+            ProcessErr::UnexpectedMsgType => write!(f, "received message of an unexpected type"),
+            ProcessErr::UnsupportedVersion(got, want) => write!(
+                f,
+                "protocol version mismatch: peer speaks {}, we speak {}",
+                got, want
+            ),
+            ProcessErr::InvalidUtf8(e) => write!(f, "message payload was not valid utf-8: {}", e),
+            ProcessErr::TruncatedMessage => write!(f, "message payload shorter than its header claimed"),
+            ProcessErr::UnknownHandle(h) => write!(f, "no spawned process with handle {}", h),
+            ProcessErr::AlreadyRunning(pid) => {
+                write!(f, "program already running with pid {}, stop it first", pid)
+            }
+            ProcessErr::PayloadTooLarge(len) => write!(
+                f,
+                "message payload of {} bytes exceeds the maximum allowed size",
+                len
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ProcessErr {}
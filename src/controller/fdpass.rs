@@ -0,0 +1,112 @@
+//! Fd-passing control-message plumbing used to hand a listening socket's
+//! raw file descriptors from the supervisor to a freshly spawned child
+//! across a graceful [`Command::Reload`](super::command::Command::Reload),
+//! modeled on the socket-activation `LISTEN_FDS`/`LISTEN_PID` contract: the
+//! child trusts the env vars instead of re-`bind()`ing, so the listener
+//! never closes between the old process exiting and the new one starting.
+//!
+//! [`send_fds`] is the supervisor's half, called from `Server`'s internal
+//! handoff on every start and reload. [`recv_fds`] is the child's half: a
+//! supervised program picks up its inherited listeners by
+//! opening `SUP_CONTROL_FD`, calling `recv_fds`, then `dup2`-ing each
+//! result onto `LISTEN_FDS_START + i` in order, matching how it would read
+//! them back out under `LISTEN_FDS`/`LISTEN_FDS_START..` itself. This
+//! crate supervises arbitrary programs and so has no child-side binary or
+//! helper exercising that half — any program opting into
+//! `preserve_sockets` is responsible for implementing it.
+
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+
+use super::error::ProcessErr;
+
+/// Number of inherited fds, read by the child instead of re-binding.
+pub const LISTEN_FDS_VAR: &str = "LISTEN_FDS";
+/// Pid the fds were handed to; a child checks this against its own pid so
+/// it doesn't pick up fds meant for a different process sharing its env.
+pub const LISTEN_PID_VAR: &str = "LISTEN_PID";
+/// Inherited sockets start right after stdio.
+pub const LISTEN_FDS_START: RawFd = 3;
+
+/// Sends `fds` as a single `SCM_RIGHTS` ancillary message over `sock`,
+/// along with one throwaway payload byte (`sendmsg` requires a non-empty
+/// iovec even when only the ancillary data matters).
+pub fn send_fds(sock: &UnixStream, fds: &[RawFd]) -> Result<(), ProcessErr> {
+    if fds.is_empty() {
+        return Ok(());
+    }
+
+    let mut payload = 0u8;
+    let iov = [libc::iovec {
+        iov_base: &mut payload as *mut _ as *mut libc::c_void,
+        iov_len: 1,
+    }];
+
+    let cmsg_space = unsafe { libc::CMSG_SPACE((fds.len() * std::mem::size_of::<RawFd>()) as u32) };
+    let mut cmsg_buf = vec![0u8; cmsg_space as usize];
+
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = iov.as_ptr() as *mut _;
+    msg.msg_iovlen = 1;
+    msg.msg_control = cmsg_buf.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = cmsg_buf.len() as _;
+
+    unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN((fds.len() * std::mem::size_of::<RawFd>()) as u32) as _;
+        std::ptr::copy_nonoverlapping(fds.as_ptr(), libc::CMSG_DATA(cmsg) as *mut RawFd, fds.len());
+    }
+
+    let sent = unsafe { libc::sendmsg(sock.as_raw_fd(), &msg, 0) };
+    if sent < 0 {
+        return Err(ProcessErr::SendFdFailed(io::Error::last_os_error().to_string()));
+    }
+    Ok(())
+}
+
+/// Receives up to `max` fds previously sent with [`send_fds`] from `sock`.
+pub fn recv_fds(sock: &UnixStream, max: usize) -> Result<Vec<RawFd>, ProcessErr> {
+    if max == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut payload = 0u8;
+    let iov = [libc::iovec {
+        iov_base: &mut payload as *mut _ as *mut libc::c_void,
+        iov_len: 1,
+    }];
+
+    let cmsg_space = unsafe { libc::CMSG_SPACE((max * std::mem::size_of::<RawFd>()) as u32) };
+    let mut cmsg_buf = vec![0u8; cmsg_space as usize];
+
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = iov.as_ptr() as *mut _;
+    msg.msg_iovlen = 1;
+    msg.msg_control = cmsg_buf.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = cmsg_buf.len() as _;
+
+    let received = unsafe { libc::recvmsg(sock.as_raw_fd(), &mut msg, 0) };
+    if received < 0 {
+        return Err(ProcessErr::RecvFdFailed(io::Error::last_os_error().to_string()));
+    }
+
+    let mut fds = Vec::new();
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let n = ((*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize)
+                    / std::mem::size_of::<RawFd>();
+                let data = libc::CMSG_DATA(cmsg) as *const RawFd;
+                for i in 0..n {
+                    fds.push(*data.add(i));
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+    Ok(fds)
+}
@@ -0,0 +1,139 @@
+//! Frames streamed back to an `Attach`/`Logs` client over an otherwise
+//! one-shot `UnixStream`. `Response`'s single 4-byte-pid-plus-blob shape
+//! only has room for one reply; a live tail needs to keep writing until
+//! the client disconnects or the program exits, so each write on the wire
+//! is tagged with which stream it came from. Frames ride as the payload
+//! of a [`super::wire`] message of type `Frame`, which already supplies
+//! the length boundary, so a frame only needs to encode its one kind byte.
+
+use super::error::ProcessErr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameKind {
+    Stdout,
+    Stderr,
+    /// Carries the program's exit code as a 4-byte big-endian `i32`
+    /// payload; the last frame an `Attach` client receives before the
+    /// supervisor closes the stream.
+    Exit,
+}
+
+impl FrameKind {
+    fn to_byte(self) -> u8 {
+        match self {
+            FrameKind::Stdout => 0,
+            FrameKind::Stderr => 1,
+            FrameKind::Exit => 2,
+        }
+    }
+
+    fn from_byte(b: u8) -> Result<Self, ProcessErr> {
+        match b {
+            0 => Ok(FrameKind::Stdout),
+            1 => Ok(FrameKind::Stderr),
+            2 => Ok(FrameKind::Exit),
+            _ => Err(ProcessErr::UnknownFrameKind(b)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub kind: FrameKind,
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    pub fn stdout(payload: Vec<u8>) -> Self {
+        Self {
+            kind: FrameKind::Stdout,
+            payload,
+        }
+    }
+
+    pub fn stderr(payload: Vec<u8>) -> Self {
+        Self {
+            kind: FrameKind::Stderr,
+            payload,
+        }
+    }
+
+    pub fn exit(code: i32) -> Self {
+        Self {
+            kind: FrameKind::Exit,
+            payload: code.to_be_bytes().to_vec(),
+        }
+    }
+
+    /// Encodes this frame as `kind_byte | payload`, to be sent as the
+    /// payload of a `wire::MsgType::Frame` message.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(1 + self.payload.len());
+        out.push(self.kind.to_byte());
+        out.extend_from_slice(&self.payload);
+        out
+    }
+
+    /// Decodes a frame from the payload of a `wire::MsgType::Frame`
+    /// message.
+    pub fn decode(bytes: Vec<u8>) -> Result<Self, ProcessErr> {
+        let (kind_byte, payload) = bytes.split_first().ok_or(ProcessErr::TruncatedMessage)?;
+        Ok(Self {
+            kind: FrameKind::from_byte(*kind_byte)?,
+            payload: payload.to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stdout_round_trips() {
+        let frame = Frame::stdout(b"hello".to_vec());
+        let decoded = Frame::decode(frame.encode()).unwrap();
+        assert_eq!(decoded.kind, FrameKind::Stdout);
+        assert_eq!(decoded.payload, b"hello");
+    }
+
+    #[test]
+    fn stderr_round_trips() {
+        let frame = Frame::stderr(b"oops".to_vec());
+        let decoded = Frame::decode(frame.encode()).unwrap();
+        assert_eq!(decoded.kind, FrameKind::Stderr);
+        assert_eq!(decoded.payload, b"oops");
+    }
+
+    #[test]
+    fn exit_round_trips_with_code() {
+        let frame = Frame::exit(42);
+        let decoded = Frame::decode(frame.encode()).unwrap();
+        assert_eq!(decoded.kind, FrameKind::Exit);
+        assert_eq!(decoded.payload, 42i32.to_be_bytes().to_vec());
+    }
+
+    #[test]
+    fn empty_payload_round_trips() {
+        let frame = Frame::stdout(Vec::new());
+        let decoded = Frame::decode(frame.encode()).unwrap();
+        assert_eq!(decoded.kind, FrameKind::Stdout);
+        assert!(decoded.payload.is_empty());
+    }
+
+    #[test]
+    fn decode_rejects_empty_bytes() {
+        match Frame::decode(Vec::new()) {
+            Err(ProcessErr::TruncatedMessage) => {}
+            other => panic!("expected TruncatedMessage, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn decode_rejects_unknown_kind_byte() {
+        match Frame::decode(vec![99, 1, 2, 3]) {
+            Err(ProcessErr::UnknownFrameKind(99)) => {}
+            other => panic!("expected UnknownFrameKind, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,7 @@
+pub mod command;
+pub mod error;
+pub mod fdpass;
+pub mod frame;
+pub mod server;
+pub mod spawn;
+pub mod wire;
@@ -0,0 +1,601 @@
+//! Owns the supervised child process and drives it through the lifecycle
+//! requested over the command socket (start/stop/restart/kill/reload/...).
+//!
+//! Sockets listed in [`SupConfig::preserve_sockets`](crate::config::config::SupConfig)
+//! are bound by the supervisor itself, not the child, so their fds survive
+//! a [`Command::Reload`](super::command::Command::Reload): they are handed
+//! to every spawned child (first start and every reload alike) over a
+//! dedicated control socket via [`fdpass`], and the child is expected to
+//! adopt them through the `LISTEN_FDS`/`LISTEN_PID` env contract instead of
+//! re-`bind()`ing.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command as StdCommand, Stdio};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use crossbeam::channel::{tick, unbounded, Receiver, Sender};
+use crossbeam::select;
+use log::{error, info, warn};
+
+use super::command::{
+    Command, CommandHandler, Request, Response, ResponseFormat, SpawnedInfo, Transport,
+};
+use super::error::ProcessErr;
+use super::fdpass::{self, LISTEN_FDS_VAR, LISTEN_PID_VAR};
+use super::frame::{Frame, FrameKind};
+use super::spawn::SpawnArgs;
+use super::wire;
+use crate::config::config::Config;
+
+/// How many of the most recent output frames are kept around for `Logs` to
+/// replay to clients that attach after the fact.
+const LOG_BUFFER_FRAMES: usize = 1024;
+/// Chunk size used when reading a child's stdout/stderr pipe.
+const READ_CHUNK: usize = 4096;
+/// How often an idle `Attach`/`Logs` connection is probed with an empty
+/// frame. Output alone can't be relied on to notice a client that
+/// disconnected without us ever failing a write to them: without a
+/// periodic write attempt, the per-connection thread would park in
+/// `rx.recv()` forever and its `Sender` would never be pruned from
+/// `OutputHub::subscribers`.
+const ATTACH_PROBE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Output fan-out shared between the pipe-reader threads and every
+/// currently `Attach`ed client, plus a bounded backlog for `Logs` replay.
+#[derive(Clone, Default)]
+struct OutputHub {
+    subscribers: Arc<Mutex<Vec<Sender<Frame>>>>,
+    backlog: Arc<Mutex<VecDeque<Frame>>>,
+}
+
+impl OutputHub {
+    fn publish(&self, frame: Frame) {
+        let mut backlog = self.backlog.lock().unwrap();
+        backlog.push_back(frame.clone());
+        if backlog.len() > LOG_BUFFER_FRAMES {
+            backlog.pop_front();
+        }
+        drop(backlog);
+
+        let mut subs = self.subscribers.lock().unwrap();
+        subs.retain(|s| s.send(frame.clone()).is_ok());
+    }
+
+    fn subscribe(&self) -> Receiver<Frame> {
+        let (tx, rx) = unbounded();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    fn replay(&self) -> Vec<Frame> {
+        self.backlog.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+fn pump(mut reader: impl Read, hub: OutputHub, to_frame: impl Fn(Vec<u8>) -> Frame) {
+    let mut buf = [0u8; READ_CHUNK];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) | Err(_) => return,
+            Ok(n) => hub.publish(to_frame(buf[..n].to_vec())),
+        }
+    }
+}
+
+/// How long the supervisor waits for a reloaded child to report readiness
+/// on the control socket before giving up and keeping the old child alive.
+const READINESS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Single byte a child writes to its end of the control socket once it has
+/// adopted the inherited fds and is ready to serve traffic.
+const READY_BYTE: u8 = 1;
+
+/// Env var telling the child which fd its end of the control socket was
+/// `dup2`'d onto, since a freshly exec'd process has no other way to find
+/// a fd it didn't open itself.
+const CONTROL_FD_VAR: &str = "SUP_CONTROL_FD";
+/// Fixed fd the child's control-socket end is duplicated onto. Picked past
+/// `LISTEN_FDS_START` so it never collides with an inherited listener.
+const CONTROL_FD: RawFd = 16;
+
+/// A running supervised program's pid, plus the thread reaping its exit.
+/// The reaper owns the `Child` itself (so `Child::wait` is only ever
+/// called from one place) and publishes a `Frame::exit` to `OutputHub`
+/// once it reaps, so `Attach`/`Logs` clients see the process end instead
+/// of just receiving keepalive probes forever. Killing the program is
+/// done by signalling `pid` directly rather than through the `Child`,
+/// since the `Child` has already been moved into the reaper thread.
+struct RunningChild {
+    pid: u32,
+    reaper: thread::JoinHandle<()>,
+}
+
+pub struct Server {
+    cfg: Config,
+    preserved: Vec<TcpListener>,
+    child: Option<RunningChild>,
+    output: OutputHub,
+    /// Ad-hoc processes launched via `Command::Spawn`, keyed by the
+    /// handle returned to the caller, so later `Status`/`Stop`/`Kill`
+    /// requests can target one of many instead of the single
+    /// preconfigured program.
+    spawned: HashMap<String, Child>,
+}
+
+impl Server {
+    pub fn new(cfg: Config) -> Result<Self, ProcessErr> {
+        let preserved = cfg
+            .sup
+            .preserve_sockets
+            .iter()
+            .map(|addr| {
+                TcpListener::bind(addr)
+                    .map_err(|e| ProcessErr::ChildSpawnFailed(format!("bind {}: {}", addr, e)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            cfg,
+            preserved,
+            child: None,
+            output: OutputHub::default(),
+            spawned: HashMap::new(),
+        })
+    }
+
+    /// Launches an arbitrary, caller-specified process under supervision
+    /// and returns the handle later `Status`/`Stop`/`Kill` requests use to
+    /// target it. Unlike [`Server::start`], a spawned process does not
+    /// inherit the preserved listeners or get captured into `Logs`/
+    /// `Attach` — it is a one-off, not the supervised program.
+    pub fn spawn(&mut self, args: SpawnArgs) -> Result<String, ProcessErr> {
+        let mut cmd = StdCommand::new(&args.program);
+        cmd.args(&args.args);
+        for (key, val) in &args.env {
+            cmd.env(key, val);
+        }
+        if let Some(cwd) = &args.cwd {
+            cmd.current_dir(cwd);
+        }
+
+        let child = cmd
+            .spawn()
+            .map_err(|e| ProcessErr::ChildSpawnFailed(e.to_string()))?;
+        let handle = format!("spawn-{}", child.id());
+        self.spawned.insert(handle.clone(), child);
+        Ok(handle)
+    }
+
+    /// Kills a process previously launched by [`Server::spawn`].
+    pub fn stop_spawned(&mut self, handle: &str) -> Result<(), ProcessErr> {
+        let mut child = self
+            .spawned
+            .remove(handle)
+            .ok_or_else(|| ProcessErr::UnknownHandle(handle.to_string()))?;
+        child
+            .kill()
+            .map_err(|e| ProcessErr::ChildSpawnFailed(e.to_string()))?;
+        let _ = child.wait();
+        Ok(())
+    }
+
+    /// Pid of a process previously launched by [`Server::spawn`].
+    pub fn spawned_pid(&self, handle: &str) -> Result<u32, ProcessErr> {
+        self.spawned
+            .get(handle)
+            .map(|c| c.id())
+            .ok_or_else(|| ProcessErr::UnknownHandle(handle.to_string()))
+    }
+
+    /// Handle/pid of every process launched via [`Server::spawn`], for
+    /// `Status`'s JSON response to surface multi-process state the
+    /// compact binary response has no room for.
+    pub fn spawned_info(&self) -> Vec<SpawnedInfo> {
+        self.spawned
+            .iter()
+            .map(|(handle, child)| SpawnedInfo {
+                handle: handle.clone(),
+                pid: child.id(),
+            })
+            .collect()
+    }
+
+    /// Tails the supervised program's stdout/stderr live; backs
+    /// `Command::Attach`. Yields frames, including a `Frame::exit` when
+    /// the program exits, until the caller drops the receiver.
+    pub fn attach(&self) -> Receiver<Frame> {
+        self.output.subscribe()
+    }
+
+    /// Replays the most recent captured output; backs `Command::Logs`.
+    pub fn logs(&self) -> Vec<Frame> {
+        self.output.replay()
+    }
+
+    /// Spawns the supervised program, handing it the preserved listeners
+    /// over a fresh control socket and waiting for its readiness signal.
+    pub fn start(&mut self) -> Result<(), ProcessErr> {
+        if let Some(running) = &self.child {
+            // Starting over a running child would silently drop (not
+            // kill) the previous program, leaking it; callers that want
+            // to replace the running program should `stop()` first, or
+            // use `Restart`, which already does so explicitly.
+            return Err(ProcessErr::AlreadyRunning(running.pid));
+        }
+        let running = self.spawn_and_handoff()?;
+        self.child = Some(running);
+        Ok(())
+    }
+
+    /// Spawns a replacement process with the same preserved listeners,
+    /// waits for it to become ready, then retires the old one. Clients of
+    /// the preserved sockets never observe a closed listener in between.
+    pub fn reload(&mut self) -> Result<(), ProcessErr> {
+        let new_running = self.spawn_and_handoff()?;
+        if let Some(old) = self.child.replace(new_running) {
+            info!("reload: retiring previous child pid {}", old.pid);
+            if let Err(e) = Self::kill_and_reap(old) {
+                warn!("reload: killing previous child failed: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Kills the supervised program, if one is running.
+    pub fn stop(&mut self) -> Result<(), ProcessErr> {
+        if let Some(running) = self.child.take() {
+            Self::kill_and_reap(running)?;
+        }
+        Ok(())
+    }
+
+    /// Signals `running`'s pid and waits for its reaper thread to confirm
+    /// the process is gone (which also publishes its `Frame::exit`).
+    /// Signalling by pid rather than through a `Child` is what lets the
+    /// reaper thread own the `Child` for the process's whole lifetime.
+    fn kill_and_reap(running: RunningChild) -> Result<(), ProcessErr> {
+        if unsafe { libc::kill(running.pid as i32, libc::SIGKILL) } < 0 {
+            let err = std::io::Error::last_os_error();
+            // ESRCH: already exited on its own: not an error, just means
+            // the reaper thread is about to finish (or already has).
+            if err.raw_os_error() != Some(libc::ESRCH) {
+                return Err(ProcessErr::ChildSpawnFailed(err.to_string()));
+            }
+        }
+        let _ = running.reaper.join();
+        Ok(())
+    }
+
+    /// Pid of the currently running program, if any.
+    pub fn pid(&self) -> Option<u32> {
+        self.child.as_ref().map(|c| c.pid)
+    }
+
+    fn spawn_and_handoff(&self) -> Result<RunningChild, ProcessErr> {
+        let (ours, theirs) =
+            UnixStream::pair().map_err(|e| ProcessErr::ChildSpawnFailed(e.to_string()))?;
+        let theirs_fd = theirs.as_raw_fd();
+
+        let mut cmd = StdCommand::new(&self.cfg.sup.program);
+        cmd.args(&self.cfg.sup.args);
+        cmd.stdout(Stdio::piped());
+        cmd.stderr(Stdio::piped());
+        cmd.env(LISTEN_FDS_VAR, self.preserved.len().to_string());
+        cmd.env(CONTROL_FD_VAR, CONTROL_FD.to_string());
+        // Runs in the forked child, before exec: name its own pid for
+        // LISTEN_PID and land its control-socket end on a fixed fd so it
+        // survives the exec and can be found by CONTROL_FD_VAR.
+        unsafe {
+            cmd.pre_exec(move || {
+                std::env::set_var(LISTEN_PID_VAR, std::process::id().to_string());
+                if libc::dup2(theirs_fd, CONTROL_FD) < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| ProcessErr::ChildSpawnFailed(e.to_string()))?;
+        drop(theirs);
+
+        // Only programs that opted into socket preservation speak the
+        // readiness handshake (dup2 `SUP_CONTROL_FD`, write `READY_BYTE`);
+        // an ordinary program with no preserved listeners never touches
+        // the control socket, so skip the handoff and wait entirely for it
+        // instead of blocking every plain `start()` for `READINESS_TIMEOUT`.
+        if !self.preserved.is_empty() {
+            let fds: Vec<_> = self.preserved.iter().map(|l| l.as_raw_fd()).collect();
+            if let Err(e) = fdpass::send_fds(&ours, &fds).and_then(|_| self.wait_ready(&ours)) {
+                // The child is already running at this point; `Child`'s
+                // `Drop` does not kill the OS process, so an early return
+                // here without cleaning it up would leak a live orphan.
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(e);
+            }
+        }
+
+        let stdout = child.stdout.take().expect("stdout was piped");
+        let stderr = child.stderr.take().expect("stderr was piped");
+        let hub = self.output.clone();
+        thread::spawn({
+            let hub = hub.clone();
+            move || pump(stdout, hub, Frame::stdout)
+        });
+        thread::spawn({
+            let hub = hub.clone();
+            move || pump(stderr, hub, Frame::stderr)
+        });
+
+        let pid = child.id();
+        let reaper = thread::spawn(move || {
+            let code = match child.wait() {
+                Ok(status) => status.code().unwrap_or(-1),
+                Err(e) => {
+                    error!("wait on child pid {} failed: {}", pid, e);
+                    -1
+                }
+            };
+            hub.publish(Frame::exit(code));
+        });
+
+        Ok(RunningChild { pid, reaper })
+    }
+
+    fn wait_ready(&self, ctrl: &UnixStream) -> Result<(), ProcessErr> {
+        use std::io::Read;
+
+        ctrl.set_read_timeout(Some(READINESS_TIMEOUT))
+            .map_err(|e| ProcessErr::ChildSpawnFailed(e.to_string()))?;
+        let mut buf = [0u8; 1];
+        let mut stream = ctrl.try_clone().map_err(|e| ProcessErr::ChildSpawnFailed(e.to_string()))?;
+        match stream.read_exact(&mut buf) {
+            Ok(()) if buf[0] == READY_BYTE => Ok(()),
+            _ => Err(ProcessErr::ReadinessTimeout),
+        }
+    }
+}
+
+/// The one `Server` a daemon process runs. `CommandHandler`'s methods take
+/// no `self` (`Request`/`Response` are plain wire data, handled the same
+/// way regardless of which listener they arrived on), so the running
+/// instance lives here instead of being threaded through.
+static INSTANCE: OnceLock<Mutex<Server>> = OnceLock::new();
+
+impl Server {
+    /// Installs `self` as the instance `CommandHandler` dispatches
+    /// against. Must be called exactly once, before any transport starts
+    /// accepting connections.
+    pub fn install(self) {
+        INSTANCE
+            .set(Mutex::new(self))
+            .unwrap_or_else(|_| panic!("server already installed"));
+    }
+
+    fn instance() -> &'static Mutex<Server> {
+        INSTANCE.get().expect("server not installed")
+    }
+}
+
+impl CommandHandler for Server {
+    fn handle_command(r: Request) -> Response {
+        let mut server = Self::instance().lock().unwrap();
+        match r.cmd {
+            Command::Start => match server.start() {
+                Ok(()) => Response::new("started".to_string(), server.pid()),
+                Err(e) => Response::new(e.to_string(), None),
+            },
+            Command::Stop | Command::Kill => match handle_of(&r.args) {
+                Ok(Some(handle)) => match server.stop_spawned(handle) {
+                    Ok(()) => Response::new(format!("stopped {}", handle), None),
+                    Err(e) => Response::new(e.to_string(), None),
+                },
+                Ok(None) => match server.stop() {
+                    Ok(()) => Response::new("stopped".to_string(), None),
+                    Err(e) => Response::new(e.to_string(), None),
+                },
+                Err(e) => Response::new(e.to_string(), None),
+            },
+            Command::Restart => match server.stop().and_then(|_| server.start()) {
+                Ok(()) => Response::new("restarted".to_string(), server.pid()),
+                Err(e) => Response::new(e.to_string(), None),
+            },
+            Command::Reload => match server.reload() {
+                Ok(()) => Response::new("reloaded".to_string(), server.pid()),
+                Err(e) => Response::new(e.to_string(), None),
+            },
+            Command::Status => match handle_of(&r.args) {
+                Ok(Some(handle)) => match server.spawned_pid(handle) {
+                    Ok(pid) => Response::new("running".to_string(), Some(pid)),
+                    Err(e) => Response::new(e.to_string(), None),
+                },
+                Ok(None) => {
+                    let message = match server.pid() {
+                        Some(_) => "running".to_string(),
+                        None => "not running".to_string(),
+                    };
+                    Response::new_with_spawned(message, server.pid(), server.spawned_info())
+                }
+                Err(e) => Response::new(e.to_string(), None),
+            },
+            // The daemon itself exits in `handle_conn`, once this reply is
+            // written.
+            Command::Exit => match server.stop() {
+                Ok(()) => Response::new("exiting".to_string(), None),
+                Err(e) => Response::new(e.to_string(), None),
+            },
+            Command::Spawn => match SpawnArgs::decode(&r.args) {
+                Ok(args) => match server.spawn(args) {
+                    Ok(handle) => Response::new(format!("spawned {}", handle), None),
+                    Err(e) => Response::new(e.to_string(), None),
+                },
+                Err(e) => Response::new(e.to_string(), None),
+            },
+            Command::Attach | Command::Logs => Response::new(
+                "attach/logs are streamed, not a single response".to_string(),
+                None,
+            ),
+            Command::Unknown => Response::new("unknown command".to_string(), None),
+        }
+    }
+
+    fn handle_stream(r: Request) -> Receiver<Frame> {
+        let server = Self::instance().lock().unwrap();
+        match r.cmd {
+            Command::Attach => server.attach(),
+            Command::Logs => {
+                let (tx, rx) = unbounded();
+                for frame in server.logs() {
+                    if tx.send(frame).is_err() {
+                        break;
+                    }
+                }
+                rx
+            }
+            _ => unbounded().1,
+        }
+    }
+}
+
+/// Reads `args` as a spawned-process handle, if `Status`/`Stop`/`Kill`
+/// included one; empty args mean the request targets the single
+/// preconfigured program instead. Present-but-invalid (non-UTF8) args is
+/// an error, not `None` — conflating the two would route a malformed or
+/// adversarial handle to the preconfigured program instead of failing,
+/// e.g. silently killing the main supervised program instead of a
+/// nonexistent spawned one.
+fn handle_of(args: &[u8]) -> Result<Option<&str>, ProcessErr> {
+    if args.is_empty() {
+        return Ok(None);
+    }
+    std::str::from_utf8(args)
+        .map(Some)
+        .map_err(|e| ProcessErr::InvalidUtf8(e.to_string()))
+}
+
+/// Runs a transport's accept loop and dispatches every connection to
+/// [`Server`]'s installed instance, generic over which concrete
+/// [`Transport`] (`unix`/`tcp`/`vsock`) the daemon was configured to
+/// listen on.
+pub fn serve_forever<Tp, T>(transport: Tp)
+where
+    Tp: Transport<T> + Send + Sync + 'static,
+    T: Read + Write + Send + 'static,
+{
+    let transport = Arc::new(transport);
+    let accept_transport = transport.clone();
+    thread::spawn(move || accept_transport.serve());
+
+    loop {
+        match transport.read() {
+            Ok(stream) => {
+                thread::spawn(move || handle_conn(stream));
+            }
+            Err(e) => error!("accept failed: {}", e),
+        }
+    }
+}
+
+fn handle_conn<T: Read + Write>(mut stream: T) {
+    if let Err(e) = wire::negotiate_server(&mut stream) {
+        error!("version handshake failed: {}", e);
+        return;
+    }
+
+    let (_, msg_type, payload) = match wire::read_message(&mut stream) {
+        Ok(m) => m,
+        Err(e) => {
+            error!("read request failed: {}", e);
+            return;
+        }
+    };
+    if msg_type != wire::MsgType::Request {
+        error!("expected a Request message, got {:?}", msg_type);
+        return;
+    }
+    let req: Request = payload.into();
+
+    match req.cmd {
+        Command::Attach | Command::Logs => {
+            // Only `Attach` should stop at a `Frame::exit`: `Logs`' backlog
+            // can span a restart and carry an older run's exit frame
+            // followed by a newer run's output, which must still be
+            // replayed in full.
+            let is_attach = matches!(req.cmd, Command::Attach);
+            let frames = Server::handle_stream(req);
+            let probe = tick(ATTACH_PROBE_INTERVAL);
+            loop {
+                select! {
+                    recv(frames) -> msg => {
+                        let frame = match msg {
+                            Ok(frame) => frame,
+                            // Sender side closed: the backlog replay finished
+                            // (`Logs`). `Attach`'s sender stays in `OutputHub`;
+                            // program exit reaches it as a `Frame::exit`.
+                            Err(_) => return,
+                        };
+                        let is_exit = frame.kind == FrameKind::Exit;
+                        let payload = frame.encode();
+                        if wire::write_message(&mut stream, wire::PROTOCOL_VERSION, wire::MsgType::Frame, &payload)
+                            .is_err()
+                        {
+                            return;
+                        }
+                        // The program exited: for `Attach`, this is the
+                        // last frame there will ever be, so stop serving
+                        // (dropping `frames` here is also what lets
+                        // `OutputHub::publish` prune this subscriber on
+                        // the next frame it sends).
+                        if is_exit && is_attach {
+                            return;
+                        }
+                    }
+                    recv(probe) -> _ => {
+                        // No real output in a while; an empty frame costs
+                        // the client nothing to ignore, but a failed write
+                        // here is how we notice it disconnected without
+                        // ever failing a write on actual output.
+                        let payload = Frame::stdout(Vec::new()).encode();
+                        if wire::write_message(&mut stream, wire::PROTOCOL_VERSION, wire::MsgType::Frame, &payload)
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+        _ => {
+            let want_json = req.format == ResponseFormat::Json;
+            let is_exit = matches!(req.cmd, Command::Exit);
+            let resp = Server::handle_command(req);
+            let (msg_type, bytes) = if want_json {
+                match resp.to_json() {
+                    Ok(bytes) => (wire::MsgType::ResponseJson, bytes),
+                    Err(e) => {
+                        error!("marshal response to json failed: {}", e);
+                        return;
+                    }
+                }
+            } else {
+                (wire::MsgType::Response, resp.into())
+            };
+            if let Err(e) = wire::write_message(&mut stream, wire::PROTOCOL_VERSION, msg_type, &bytes) {
+                error!("write response failed: {}", e);
+            }
+            if is_exit {
+                info!("exit requested, daemon exiting");
+                std::process::exit(0);
+            }
+        }
+    }
+}
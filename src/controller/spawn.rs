@@ -0,0 +1,30 @@
+//! Payload carried by `Command::Spawn`: enough to launch an arbitrary,
+//! caller-specified one-off process under supervision, the way `Start`
+//! launches the single preconfigured program. Serialized as the `args`
+//! body of a [`super::command::Request`], now that [`super::wire`] gives
+//! every message an explicit length instead of a single opcode byte.
+
+use serde::{Deserialize, Serialize};
+
+use super::error::ProcessErr;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SpawnArgs {
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: Vec<(String, String)>,
+    #[serde(default)]
+    pub cwd: Option<String>,
+}
+
+impl SpawnArgs {
+    pub fn encode(&self) -> Result<Vec<u8>, ProcessErr> {
+        serde_json::to_vec(self).map_err(|e| ProcessErr::InvalidUtf8(e.to_string()))
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ProcessErr> {
+        serde_json::from_slice(bytes).map_err(|e| ProcessErr::InvalidUtf8(e.to_string()))
+    }
+}
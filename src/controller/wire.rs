@@ -0,0 +1,217 @@
+//! Versioned, length-framed wire protocol that everything on the command
+//! socket rides in. Before this module, `Command`/`Request`/`Response`
+//! were decoded by slicing a raw `Vec<u8>` directly — `Response::from`
+//! panicked on a short read and `unmarshal_msg` panicked on non-UTF8 —
+//! so a truncated or stale-client write could take the daemon down. Every
+//! message is now `magic | version | msg_type | len (u32 BE) | payload`,
+//! decoded through `Result`, with an explicit version handshake so a
+//! mismatched client/daemon build fails loudly instead of misparsing.
+
+use std::io::{Read, Write};
+
+use super::error::ProcessErr;
+
+/// First byte of every message; rejects anything that isn't this protocol
+/// at all (e.g. a stray HTTP request hitting the socket).
+pub const MAGIC: u8 = 0xAF;
+/// Current protocol version. Bump whenever the header or an existing
+/// message's payload shape changes incompatibly.
+pub const PROTOCOL_VERSION: u8 = 1;
+
+const HEADER_LEN: usize = 7;
+
+/// Upper bound on a single message's payload length. Without this, a peer
+/// can put an arbitrary u32 in the length field and make `read_message`
+/// allocate up to ~4GiB before a single payload byte has even arrived,
+/// trivially OOMing or aborting the daemon over a bare TCP/vsock socket.
+/// 16MiB is far above any payload the daemon itself sends: output frames,
+/// `Logs` replay included, go out one per message, each at most one pipe
+/// read plus its kind byte.
+const MAX_PAYLOAD_LEN: u32 = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MsgType {
+    Hello,
+    Request,
+    Response,
+    Frame,
+    /// Same reply as `Response`, but the payload is JSON (see
+    /// [`super::command::Response::to_json`]) instead of the compact
+    /// binary marshal, for a client that asked for `ResponseFormat::Json`.
+    ResponseJson,
+}
+
+impl MsgType {
+    fn to_byte(self) -> u8 {
+        match self {
+            MsgType::Hello => 0,
+            MsgType::Request => 1,
+            MsgType::Response => 2,
+            MsgType::Frame => 3,
+            MsgType::ResponseJson => 4,
+        }
+    }
+
+    fn from_byte(b: u8) -> Result<Self, ProcessErr> {
+        match b {
+            0 => Ok(MsgType::Hello),
+            1 => Ok(MsgType::Request),
+            2 => Ok(MsgType::Response),
+            3 => Ok(MsgType::Frame),
+            4 => Ok(MsgType::ResponseJson),
+            _ => Err(ProcessErr::UnknownMsgType(b)),
+        }
+    }
+}
+
+/// Writes `payload` as a single framed message: `magic | version |
+/// msg_type | len (u32 BE) | payload`.
+pub fn write_message(
+    w: &mut impl Write,
+    version: u8,
+    msg_type: MsgType,
+    payload: &[u8],
+) -> Result<(), ProcessErr> {
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.push(MAGIC);
+    header.push(version);
+    header.push(msg_type.to_byte());
+    header.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+
+    w.write_all(&header)
+        .and_then(|_| w.write_all(payload))
+        .map_err(|e| ProcessErr::WriteToStreamFailed(e.to_string()))
+}
+
+/// Reads one framed message, validating the magic byte and returning the
+/// message's own `version` alongside its type and payload so callers can
+/// react to a version mismatch instead of just failing to parse.
+pub fn read_message(r: &mut impl Read) -> Result<(u8, MsgType, Vec<u8>), ProcessErr> {
+    let mut header = [0u8; HEADER_LEN];
+    read_exact_eof(r, &mut header)?;
+
+    if header[0] != MAGIC {
+        return Err(ProcessErr::BadMagic(header[0]));
+    }
+    let version = header[1];
+    let msg_type = MsgType::from_byte(header[2])?;
+    let len = u32::from_be_bytes(header[3..HEADER_LEN].try_into().unwrap());
+    if len > MAX_PAYLOAD_LEN {
+        return Err(ProcessErr::PayloadTooLarge(len));
+    }
+
+    let mut payload = vec![0u8; len as usize];
+    r.read_exact(&mut payload)
+        .map_err(|e| ProcessErr::ReadFromChannelFail(e.to_string()))?;
+
+    Ok((version, msg_type, payload))
+}
+
+/// Client side of the version handshake: send our version as a `Hello`,
+/// then read the daemon's own `Hello` back. Fails if the two builds don't
+/// speak a compatible protocol version rather than letting a mismatched
+/// pair silently misparse each other's messages.
+pub fn negotiate_client(stream: &mut (impl Read + Write)) -> Result<(), ProcessErr> {
+    write_message(stream, PROTOCOL_VERSION, MsgType::Hello, &[])?;
+    let (peer_version, msg_type, _) = read_message(stream)?;
+    if msg_type != MsgType::Hello {
+        return Err(ProcessErr::UnexpectedMsgType);
+    }
+    check_compatible(peer_version)
+}
+
+/// Daemon side of the version handshake, mirroring [`negotiate_client`].
+pub fn negotiate_server(stream: &mut (impl Read + Write)) -> Result<(), ProcessErr> {
+    let (peer_version, msg_type, _) = read_message(stream)?;
+    if msg_type != MsgType::Hello {
+        return Err(ProcessErr::UnexpectedMsgType);
+    }
+    write_message(stream, PROTOCOL_VERSION, MsgType::Hello, &[])?;
+    check_compatible(peer_version)
+}
+
+fn check_compatible(peer_version: u8) -> Result<(), ProcessErr> {
+    if peer_version != PROTOCOL_VERSION {
+        return Err(ProcessErr::UnsupportedVersion(peer_version, PROTOCOL_VERSION));
+    }
+    Ok(())
+}
+
+fn read_exact_eof(r: &mut impl Read, buf: &mut [u8]) -> Result<(), ProcessErr> {
+    use std::io::ErrorKind;
+    match r.read_exact(buf) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Err(ProcessErr::StreamClosed),
+        Err(e) => Err(ProcessErr::ReadFromChannelFail(e.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn write_then_read_round_trips() {
+        let mut buf = Cursor::new(Vec::new());
+        write_message(&mut buf, PROTOCOL_VERSION, MsgType::Request, b"hello").unwrap();
+
+        buf.set_position(0);
+        let (version, msg_type, payload) = read_message(&mut buf).unwrap();
+        assert_eq!(version, PROTOCOL_VERSION);
+        assert_eq!(msg_type, MsgType::Request);
+        assert_eq!(payload, b"hello");
+    }
+
+    #[test]
+    fn write_then_read_round_trips_empty_payload() {
+        let mut buf = Cursor::new(Vec::new());
+        write_message(&mut buf, PROTOCOL_VERSION, MsgType::Frame, &[]).unwrap();
+
+        buf.set_position(0);
+        let (_, msg_type, payload) = read_message(&mut buf).unwrap();
+        assert_eq!(msg_type, MsgType::Frame);
+        assert!(payload.is_empty());
+    }
+
+    #[test]
+    fn read_rejects_bad_magic() {
+        let mut buf = Cursor::new(vec![0x00, PROTOCOL_VERSION, MsgType::Hello.to_byte(), 0, 0, 0, 0]);
+        match read_message(&mut buf) {
+            Err(ProcessErr::BadMagic(0x00)) => {}
+            other => panic!("expected BadMagic, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn read_rejects_truncated_header() {
+        let mut buf = Cursor::new(vec![MAGIC, PROTOCOL_VERSION]);
+        match read_message(&mut buf) {
+            Err(ProcessErr::StreamClosed) => {}
+            other => panic!("expected StreamClosed, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn read_rejects_oversized_payload_length() {
+        let mut header = vec![MAGIC, PROTOCOL_VERSION, MsgType::Request.to_byte()];
+        header.extend_from_slice(&(MAX_PAYLOAD_LEN + 1).to_be_bytes());
+        let mut buf = Cursor::new(header);
+        match read_message(&mut buf) {
+            Err(ProcessErr::PayloadTooLarge(len)) => assert_eq!(len, MAX_PAYLOAD_LEN + 1),
+            other => panic!("expected PayloadTooLarge, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn negotiate_rejects_version_mismatch() {
+        assert!(check_compatible(PROTOCOL_VERSION).is_ok());
+        match check_compatible(PROTOCOL_VERSION + 1) {
+            Err(ProcessErr::UnsupportedVersion(got, want)) => {
+                assert_eq!(got, PROTOCOL_VERSION + 1);
+                assert_eq!(want, PROTOCOL_VERSION);
+            }
+            other => panic!("expected UnsupportedVersion, got {:?}", other),
+        }
+    }
+}